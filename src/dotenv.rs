@@ -0,0 +1,185 @@
+/*
+ * Copyright (c) 2019 Lucas Vasilakopoulos
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! A small, standalone `.env` file parser.
+//!
+//! Supports `#` comments, blank lines, an optional `export ` prefix, and
+//! single/double-quoted values. Double-quoted values additionally support
+//! `\n`, `\t`, `\r`, `\"` and `\\` escapes.
+
+use failure::Fail;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// An error encountered while reading or parsing a `.env` file.
+#[derive(Debug, Fail)]
+pub enum DotenvError {
+    #[fail(display = "unable to read dotenv file: {}", _0)]
+    Io(io::Error),
+    #[fail(display = "line {}: {}", line, message)]
+    Parse { line: usize, message: String },
+}
+
+/// Reads and parses the `.env` file at `path`.
+///
+/// Every malformed line is collected rather than stopping at the first one,
+/// so callers see the full set of problems in one pass.
+pub fn parse_file<P: AsRef<Path>>(path: P) -> Result<Vec<(String, String)>, Vec<DotenvError>> {
+    let contents = fs::read_to_string(path).map_err(|err| vec![DotenvError::Io(err)])?;
+
+    parse(&contents)
+}
+
+/// Parses the `KEY=VALUE` contents of a `.env` file.
+pub fn parse(contents: &str) -> Result<Vec<(String, String)>, Vec<DotenvError>> {
+    let mut entries = Vec::new();
+    let mut errors = Vec::new();
+
+    for (index, raw_line) in contents.lines().enumerate() {
+        let line = raw_line.trim();
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let line_number = index + 1;
+        let line = line.strip_prefix("export ").unwrap_or(line);
+
+        match line.find('=') {
+            Some(eq) => {
+                let key = line[..eq].trim();
+                let raw_value = line[eq + 1..].trim();
+
+                if key.is_empty() {
+                    errors.push(DotenvError::Parse {
+                        line: line_number,
+                        message: "missing key before '='".to_string(),
+                    });
+                    continue;
+                }
+
+                match unquote(raw_value) {
+                    Ok(value) => entries.push((key.to_string(), value)),
+                    Err(message) => errors.push(DotenvError::Parse {
+                        line: line_number,
+                        message,
+                    }),
+                }
+            }
+            None => errors.push(DotenvError::Parse {
+                line: line_number,
+                message: format!("expected KEY=VALUE, found '{}'", line),
+            }),
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(entries)
+    } else {
+        Err(errors)
+    }
+}
+
+/// Strips a single layer of matching quotes from `value`, unescaping the
+/// contents of a double-quoted value. An unterminated quote is a parse
+/// error; an unquoted value is returned as-is.
+fn unquote(value: &str) -> Result<String, String> {
+    let bytes = value.as_bytes();
+
+    if bytes.is_empty() {
+        return Ok(String::new());
+    }
+
+    let quote = bytes[0];
+
+    if quote != b'"' && quote != b'\'' {
+        return Ok(value.to_string());
+    }
+
+    if bytes.len() < 2 || bytes[bytes.len() - 1] != quote {
+        return Err(format!("unterminated quote in '{}'", value));
+    }
+
+    let inner = &value[1..value.len() - 1];
+
+    if quote == b'"' {
+        Ok(unescape(inner))
+    } else {
+        Ok(inner.to_string())
+    }
+}
+
+fn unescape(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    let mut chars = value.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some('t') => result.push('\t'),
+            Some('r') => result.push('\r'),
+            Some('"') => result.push('"'),
+            Some('\\') => result.push('\\'),
+            Some(other) => {
+                result.push('\\');
+                result.push(other);
+            }
+            None => result.push('\\'),
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_unquoted_and_single_quoted_values_literally() {
+        let entries = parse("PLAIN=value\nSINGLE='literal ${NOT_EXPANDED}'").unwrap();
+
+        assert_eq!(entries[0], ("PLAIN".to_string(), "value".to_string()));
+        assert_eq!(
+            entries[1],
+            (
+                "SINGLE".to_string(),
+                "literal ${NOT_EXPANDED}".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn unescapes_double_quoted_values() {
+        let entries = parse(r#"QUOTED="line one\nline two\t\"quoted\"\\done""#).unwrap();
+
+        assert_eq!(
+            entries[0],
+            (
+                "QUOTED".to_string(),
+                "line one\nline two\t\"quoted\"\\done".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn reports_an_unterminated_quote_as_a_parse_error() {
+        let errors = parse("BROKEN=\"unterminated").unwrap_err();
+
+        assert!(matches!(
+            &errors[0],
+            DotenvError::Parse { line: 1, message } if message.contains("unterminated quote")
+        ));
+    }
+}
@@ -0,0 +1,277 @@
+/*
+ * Copyright (c) 2019 Lucas Vasilakopoulos
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Expansion of `${VAR}` / `$VAR` (or `%VAR%` on Windows) references found
+//! inside loaded values, resolving against the map being built and, failing
+//! that, the ambient process environment.
+
+use crate::EnvError;
+use std::collections::HashMap;
+use std::env;
+
+/// The maximum number of fixed-point passes performed over the map before a
+/// still-changing value is treated as an unresolvable cycle.
+const MAX_ITERATIONS: usize = 32;
+
+/// The reference syntax to recognize while expanding values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExpansionType {
+    /// `${VAR}` and bare `$VAR`.
+    Unix,
+    /// `%VAR%`.
+    Windows,
+}
+
+/// Controls how [`expand_all`] resolves and reports references.
+#[derive(Debug, Clone, Copy)]
+pub struct ExpansionOptions {
+    /// The reference syntax to look for.
+    pub expansion_type: ExpansionType,
+    /// When `true`, a reference that cannot be resolved anywhere is left in
+    /// the output untouched. When `false` (the default), it is replaced with
+    /// an empty string.
+    pub keep_unresolved: bool,
+}
+
+impl Default for ExpansionOptions {
+    fn default() -> Self {
+        ExpansionOptions {
+            expansion_type: ExpansionType::Unix,
+            keep_unresolved: false,
+        }
+    }
+}
+
+/// Expands every value in `env_map` in place, substituting references first
+/// from `env_map` itself, then from the ambient environment.
+///
+/// Because a value may reference a variable that is itself expanded later in
+/// the map, resolution runs to a fixed point. A value that keeps changing
+/// past [`MAX_ITERATIONS`] is reported as an unresolved cycle rather than
+/// looping forever.
+pub(crate) fn expand_all(
+    env_map: &mut HashMap<String, String>,
+    options: ExpansionOptions,
+) -> HashMap<String, EnvError> {
+    let mut errors = HashMap::new();
+
+    for _ in 0..MAX_ITERATIONS {
+        let mut changed = false;
+        let keys: Vec<String> = env_map.keys().cloned().collect();
+
+        for key in keys {
+            let value = env_map[&key].clone();
+            let expanded = expand_value(&value, env_map, options.expansion_type, true);
+
+            if expanded != value {
+                env_map.insert(key, expanded);
+                changed = true;
+            }
+        }
+
+        if !changed {
+            break;
+        }
+    }
+
+    let keys: Vec<String> = env_map.keys().cloned().collect();
+
+    for key in keys {
+        let value = env_map[&key].clone();
+
+        if references(&value, options.expansion_type).is_empty() {
+            continue;
+        }
+
+        if still_resolvable(&value, env_map, options.expansion_type) {
+            errors.insert(
+                key,
+                EnvError::UnresolvedReference(format!(
+                    "'{}' could not be resolved after {} iterations (possible cycle)",
+                    value, MAX_ITERATIONS
+                )),
+            );
+        } else {
+            let resolved = expand_value(&value, env_map, options.expansion_type, options.keep_unresolved);
+            env_map.insert(key, resolved);
+        }
+    }
+
+    errors
+}
+
+fn still_resolvable(
+    value: &str,
+    env_map: &HashMap<String, String>,
+    expansion_type: ExpansionType,
+) -> bool {
+    references(value, expansion_type)
+        .iter()
+        .any(|(_, name)| env_map.contains_key(name) || env::var(name).is_ok())
+}
+
+/// Replaces every reference in `value` with its resolved value. References
+/// that resolve to nothing are either removed (replaced with an empty
+/// string) or left untouched, depending on `keep_unresolved`.
+fn expand_value(
+    value: &str,
+    env_map: &HashMap<String, String>,
+    expansion_type: ExpansionType,
+    keep_unresolved: bool,
+) -> String {
+    let refs = references(value, expansion_type);
+
+    if refs.is_empty() {
+        return value.to_string();
+    }
+
+    let mut result = String::with_capacity(value.len());
+    let mut cursor = 0;
+
+    for (range, name) in refs {
+        result.push_str(&value[cursor..range.start]);
+
+        match env_map.get(&name).cloned().or_else(|| env::var(&name).ok()) {
+            Some(resolved) => result.push_str(&resolved),
+            None => {
+                if keep_unresolved {
+                    result.push_str(&value[range.start..range.end]);
+                }
+            }
+        }
+
+        cursor = range.end;
+    }
+
+    result.push_str(&value[cursor..]);
+    result
+}
+
+/// Finds every `(byte range, variable name)` reference in `value` for the
+/// given `expansion_type`.
+fn references(value: &str, expansion_type: ExpansionType) -> Vec<(std::ops::Range<usize>, String)> {
+    match expansion_type {
+        ExpansionType::Unix => unix_references(value),
+        ExpansionType::Windows => windows_references(value),
+    }
+}
+
+fn unix_references(value: &str) -> Vec<(std::ops::Range<usize>, String)> {
+    let bytes = value.as_bytes();
+    let mut found = Vec::new();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] != b'$' {
+            i += 1;
+            continue;
+        }
+
+        if let Some(&b'{') = bytes.get(i + 1) {
+            if let Some(end) = value[i + 2..].find('}') {
+                let name_start = i + 2;
+                let name_end = name_start + end;
+                found.push((i..name_end + 1, value[name_start..name_end].to_string()));
+                i = name_end + 1;
+                continue;
+            }
+        } else {
+            let name_start = i + 1;
+            let mut name_end = name_start;
+
+            while name_end < bytes.len() && is_name_byte(bytes[name_end]) {
+                name_end += 1;
+            }
+
+            if name_end > name_start {
+                found.push((i..name_end, value[name_start..name_end].to_string()));
+                i = name_end;
+                continue;
+            }
+        }
+
+        i += 1;
+    }
+
+    found
+}
+
+fn windows_references(value: &str) -> Vec<(std::ops::Range<usize>, String)> {
+    let indices: Vec<usize> = value
+        .char_indices()
+        .filter(|&(_, c)| c == '%')
+        .map(|(i, _)| i)
+        .collect();
+
+    let mut found = Vec::new();
+    let mut i = 0;
+
+    while i + 1 < indices.len() {
+        let open = indices[i];
+        let close = indices[i + 1];
+
+        if close > open + 1 {
+            found.push((open..close + 1, value[open + 1..close].to_string()));
+            i += 2;
+        } else {
+            i += 1;
+        }
+    }
+
+    found
+}
+
+fn is_name_byte(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || byte == b'_'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expands_a_resolvable_chain() {
+        let mut env_map = HashMap::new();
+        env_map.insert("GREETING".to_string(), "hello ${NAME}".to_string());
+        env_map.insert("NAME".to_string(), "${FIRST} ${LAST}".to_string());
+        env_map.insert("FIRST".to_string(), "ada".to_string());
+        env_map.insert("LAST".to_string(), "lovelace".to_string());
+
+        let errors = expand_all(&mut env_map, ExpansionOptions::default());
+
+        assert!(errors.is_empty());
+        assert_eq!(env_map["GREETING"], "hello ada lovelace");
+    }
+
+    #[test]
+    fn reports_a_cycle_as_unresolved() {
+        let mut env_map = HashMap::new();
+        env_map.insert("A".to_string(), "${B}".to_string());
+        env_map.insert("B".to_string(), "${A}".to_string());
+
+        let errors = expand_all(&mut env_map, ExpansionOptions::default());
+
+        assert!(matches!(
+            errors.get("A").or_else(|| errors.get("B")),
+            Some(EnvError::UnresolvedReference(_))
+        ));
+    }
+
+    #[test]
+    fn still_resolvable_checks_the_map_then_the_environment() {
+        let mut env_map = HashMap::new();
+        env_map.insert("KNOWN".to_string(), "value".to_string());
+
+        assert!(still_resolvable("${KNOWN}", &env_map, ExpansionType::Unix));
+        assert!(!still_resolvable(
+            "${MISSING_ENTIRELY}",
+            &env_map,
+            ExpansionType::Unix
+        ));
+    }
+}
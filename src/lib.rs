@@ -12,14 +12,92 @@
 //! This crate provides the capability to load desired environment variables
 //! into a map, merging with any optional defaults specified by the input map.
 
+mod dotenv;
+mod expand;
+mod loader;
+mod typed;
+
+pub use dotenv::DotenvError;
+pub use expand::{ExpansionOptions, ExpansionType};
+pub use loader::{CollectResult, Defaults, DotenvFile, Environment, Loader, Source};
+pub use typed::{bool_validator, load_typed, number_validator, vec_validator, TypedEnv, Validator};
+
 use failure::Fail;
 use std::collections::HashMap;
 use std::env;
 use std::env::VarError;
+use std::path::Path;
 
-type EnvVars<'a, T> = &'a HashMap<&'a str, Option<&'a str>, T>;
+type EnvVars<'a, T> = &'a HashMap<&'a str, VarSpec<'a>, T>;
 type EnvMap = HashMap<String, String>;
-type EnvErrors = HashMap<String, VarError>;
+type EnvErrors = HashMap<String, EnvError>;
+
+/// The fallback configuration for a single requested environment variable.
+///
+/// Besides the map's own key (the variable's primary name), a variable may
+/// carry `aliases` — alternative names tried, in order, before falling back
+/// to `default`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct VarSpec<'a> {
+    pub aliases: &'a [&'a str],
+    pub default: Option<&'a str>,
+}
+
+impl<'a> VarSpec<'a> {
+    /// A variable with no aliases, optionally defaulting to `default`.
+    pub fn new(default: Option<&'a str>) -> Self {
+        VarSpec {
+            aliases: &[],
+            default,
+        }
+    }
+
+    /// A variable that additionally falls back to `aliases`, in order,
+    /// before `default`.
+    pub fn with_aliases(aliases: &'a [&'a str], default: Option<&'a str>) -> Self {
+        VarSpec { aliases, default }
+    }
+}
+
+impl<'a> From<Option<&'a str>> for VarSpec<'a> {
+    fn from(default: Option<&'a str>) -> Self {
+        VarSpec::new(default)
+    }
+}
+
+/// Resolves `name`, then each of `aliases` in order, against the ambient
+/// environment, returning the first present value.
+fn resolve_var(name: &str, aliases: &[&str]) -> Result<String, VarError> {
+    match env::var(name) {
+        Err(VarError::NotPresent) => {
+            for &alias in aliases {
+                match env::var(alias) {
+                    Err(VarError::NotPresent) => continue,
+                    result => return result,
+                }
+            }
+
+            Err(VarError::NotPresent)
+        }
+        result => result,
+    }
+}
+
+/// An error encountered while resolving a single environment variable: while
+/// reading it from the environment, while coercing its value to a requested
+/// type, while expanding references in its value, or while reading a `.env`
+/// file.
+#[derive(Debug, Fail)]
+pub enum EnvError {
+    #[fail(display = "{}", _0)]
+    Var(VarError),
+    #[fail(display = "unable to parse value as the requested type: {}", _0)]
+    Parse(String),
+    #[fail(display = "unable to resolve reference(s): {}", _0)]
+    UnresolvedReference(String),
+    #[fail(display = "{}", _0)]
+    Dotenv(DotenvError),
+}
 
 #[derive(Debug, Fail)]
 #[fail(display = "error(s) occurred loading environment variables")]
@@ -27,6 +105,16 @@ pub struct LoadError {
     env_errors: EnvErrors,
 }
 
+impl LoadError {
+    pub(crate) fn new(env_errors: EnvErrors) -> Self {
+        LoadError { env_errors }
+    }
+
+    pub(crate) fn into_env_errors(self) -> EnvErrors {
+        self.env_errors
+    }
+}
+
 /// Loads variables from the environment, falling back and setting defaults if
 /// they are provided. It returns an error for any missing required variables.
 ///
@@ -37,13 +125,16 @@ pub struct LoadError {
 /// Examples:
 ///
 /// ```
-/// use env_map::load;
+/// use env_map::{load, VarSpec};
 /// use std::collections::HashMap;
 ///
-/// let env_vars: HashMap<&str, Option<&str>> = [("REQUIRED", None), ("OPTIONAL", Some("default"))]
-///     .iter()
-///     .cloned()
-///     .collect();
+/// let env_vars: HashMap<&str, VarSpec> = [
+///     ("REQUIRED", VarSpec::new(None)),
+///     ("OPTIONAL", VarSpec::new(Some("default"))),
+/// ]
+/// .iter()
+/// .cloned()
+/// .collect();
 ///
 /// match load(&env_vars) {
 ///     Ok(env) => println!("{:?}", env),
@@ -51,36 +142,277 @@ pub struct LoadError {
 /// }
 /// ```
 pub fn load<T>(env_vars: EnvVars<T>) -> Result<EnvMap, LoadError> {
+    load_internal(env_vars, true, |_, _| None, |_, _| Ok(()))
+}
+
+/// Loads variables the same way [`load`] does, but never calls
+/// `env::set_var`: a default used to satisfy a missing variable is returned
+/// in the map without being written back to the process environment.
+///
+/// This is useful for library consumers and for tests that load several
+/// different configurations in the same process without leaking state
+/// between them.
+///
+/// Examples:
+///
+/// ```
+/// use env_map::{load_pure, VarSpec};
+/// use std::collections::HashMap;
+///
+/// let env_vars: HashMap<&str, VarSpec> = [("OPTIONAL", VarSpec::new(Some("default")))]
+///     .iter()
+///     .cloned()
+///     .collect();
+///
+/// let env = load_pure(&env_vars).unwrap();
+/// assert_eq!(env["OPTIONAL"], "default");
+/// assert!(std::env::var("OPTIONAL").is_err());
+/// ```
+pub fn load_pure<T>(env_vars: EnvVars<T>) -> Result<EnvMap, LoadError> {
+    load_internal(env_vars, false, |_, _| None, |_, _| Ok(()))
+}
+
+/// Resolves every requested variable, shared by [`load`]/[`load_pure`],
+/// [`load_with_file`]/[`load_with_file_pure`] and
+/// [`load_typed`](crate::load_typed).
+///
+/// For each variable, precedence is: the real environment (via `aliases`
+/// too), then `fallback(name, spec.aliases)`, then `spec.default`, then a
+/// missing-variable error. `fallback` lets a caller splice in another source
+/// (e.g. a parsed `.env` file) between the environment and the inline
+/// default; callers with nothing to add pass `|_, _| None`. A value used to
+/// satisfy a missing variable, whether from `fallback` or `spec.default`, is
+/// written back with `env::set_var` only when `write_back` is `true`. Every
+/// resolved value is passed through `validate`, which can turn a value that
+/// fails a type check into its own `EnvError`, landing in the same
+/// aggregated map as missing-variable errors.
+pub(crate) fn load_internal<T>(
+    env_vars: EnvVars<T>,
+    write_back: bool,
+    fallback: impl Fn(&str, &[&str]) -> Option<String>,
+    validate: impl Fn(&str, &str) -> Result<(), EnvError>,
+) -> Result<EnvMap, LoadError> {
     let mut env_map: EnvMap = HashMap::new();
     let mut env_errors: EnvErrors = HashMap::new();
 
-    for (&name, &option) in env_vars {
+    for (&name, &spec) in env_vars {
         let key = name.to_string();
 
-        match env::var(&key) {
-            Ok(value) => {
-                env_map.insert(key, value);
+        let resolved = match resolve_var(name, spec.aliases) {
+            Ok(value) => Ok((value, false)),
+            Err(VarError::NotPresent) => {
+                match fallback(name, spec.aliases).or_else(|| spec.default.map(str::to_string)) {
+                    Some(value) => Ok((value, true)),
+                    None => Err(EnvError::Var(VarError::NotPresent)),
+                }
             }
-            Err(err) => match err {
-                VarError::NotPresent => match option {
-                    Some(value) => {
-                        env::set_var(&key, value);
-                        env_map.insert(key, value.to_string());
-                    }
-                    None => {
-                        env_errors.insert(key, err);
+            Err(err) => Err(EnvError::Var(err)),
+        };
+
+        match resolved {
+            Ok((value, is_default)) => match validate(name, &value) {
+                Ok(()) => {
+                    if is_default && write_back {
+                        env::set_var(&key, &value);
                     }
-                },
-                _ => {
+
+                    env_map.insert(key, value);
+                }
+                Err(err) => {
                     env_errors.insert(key, err);
                 }
             },
+            Err(err) => {
+                env_errors.insert(key, err);
+            }
         }
     }
 
     if env_errors.is_empty() {
         Ok(env_map)
     } else {
-        Err(LoadError { env_errors })
+        Err(LoadError::new(env_errors))
+    }
+}
+
+/// Loads variables the same way [`load`] does, then expands `${VAR}` / `$VAR`
+/// (or `%VAR%`, depending on `options.expansion_type`) references found in
+/// the resulting values.
+///
+/// A reference is resolved first against the variables loaded in this call,
+/// then against the ambient process environment. Because one variable may
+/// reference another loaded in the same call, expansion runs to a fixed
+/// point; a value that cannot converge (an unresolved cycle) is reported
+/// through the same `LoadError`/`EnvErrors` mechanism as any other loading
+/// failure.
+///
+/// Examples:
+///
+/// ```
+/// use env_map::{load_expanded, ExpansionOptions, VarSpec};
+/// use std::collections::HashMap;
+///
+/// let env_vars: HashMap<&str, VarSpec> = [
+///     ("GREETING", VarSpec::new(Some("hello ${NAME}"))),
+///     ("NAME", VarSpec::new(Some("world"))),
+/// ]
+/// .iter()
+/// .cloned()
+/// .collect();
+///
+/// let env = load_expanded(&env_vars, ExpansionOptions::default()).unwrap();
+/// assert_eq!(env["GREETING"], "hello world");
+/// ```
+pub fn load_expanded<T>(
+    env_vars: EnvVars<T>,
+    options: ExpansionOptions,
+) -> Result<EnvMap, LoadError> {
+    let mut env_map = load(env_vars)?;
+    let env_errors = expand::expand_all(&mut env_map, options);
+
+    if env_errors.is_empty() {
+        Ok(env_map)
+    } else {
+        Err(LoadError::new(env_errors))
+    }
+}
+
+/// Loads variables the same way [`load`] does, but falls back to entries
+/// parsed from a `.env` file at `path` before falling back to the inline
+/// default.
+///
+/// Precedence for each requested variable is: the real environment, then the
+/// `.env` file, then the inline default, then a missing-variable error. If
+/// the file itself is unreadable or contains malformed lines, those are
+/// reported alongside any missing-variable errors through the same
+/// `LoadError`.
+///
+/// Examples:
+///
+/// ```no_run
+/// use env_map::{load_with_file, VarSpec};
+/// use std::collections::HashMap;
+///
+/// let env_vars: HashMap<&str, VarSpec> = [("DATABASE_URL", VarSpec::new(None))]
+///     .iter()
+///     .cloned()
+///     .collect();
+///
+/// match load_with_file(&env_vars, ".env") {
+///     Ok(env) => println!("{:?}", env),
+///     Err(err) => println!("{:?}", err),
+/// }
+/// ```
+pub fn load_with_file<T, P: AsRef<Path>>(
+    env_vars: EnvVars<T>,
+    path: P,
+) -> Result<EnvMap, LoadError> {
+    load_with_file_internal(env_vars, path.as_ref(), true)
+}
+
+/// Loads variables the same way [`load_with_file`] does, but never calls
+/// `env::set_var`, mirroring [`load_pure`]'s relationship to [`load`].
+///
+/// Examples:
+///
+/// ```no_run
+/// use env_map::{load_with_file_pure, VarSpec};
+/// use std::collections::HashMap;
+///
+/// let env_vars: HashMap<&str, VarSpec> = [("DATABASE_URL", VarSpec::new(None))]
+///     .iter()
+///     .cloned()
+///     .collect();
+///
+/// match load_with_file_pure(&env_vars, ".env") {
+///     Ok(env) => println!("{:?}", env),
+///     Err(err) => println!("{:?}", err),
+/// }
+/// ```
+pub fn load_with_file_pure<T, P: AsRef<Path>>(
+    env_vars: EnvVars<T>,
+    path: P,
+) -> Result<EnvMap, LoadError> {
+    load_with_file_internal(env_vars, path.as_ref(), false)
+}
+
+/// Shared by [`load_with_file`] and [`load_with_file_pure`]: parses `path`
+/// into a `.env` layer, then delegates to [`load_internal`] with that layer
+/// spliced in as a fallback ahead of each variable's inline default.
+fn load_with_file_internal<T>(
+    env_vars: EnvVars<T>,
+    path: &Path,
+    write_back: bool,
+) -> Result<EnvMap, LoadError> {
+    let mut env_errors: EnvErrors = HashMap::new();
+
+    let dotenv_entries: HashMap<String, String> = match dotenv::parse_file(path) {
+        Ok(entries) => entries.into_iter().collect(),
+        Err(errors) => {
+            for (index, error) in errors.into_iter().enumerate() {
+                env_errors.insert(
+                    format!("{}:{}", path.display(), index),
+                    EnvError::Dotenv(error),
+                );
+            }
+
+            HashMap::new()
+        }
+    };
+
+    let result = load_internal(
+        env_vars,
+        write_back,
+        |name, aliases| {
+            dotenv_entries
+                .get(name)
+                .or_else(|| aliases.iter().find_map(|alias| dotenv_entries.get(*alias)))
+                .cloned()
+        },
+        |_, _| Ok(()),
+    );
+
+    match result {
+        Ok(env_map) if env_errors.is_empty() => Ok(env_map),
+        Ok(_) => Err(LoadError::new(env_errors)),
+        Err(load_err) => {
+            env_errors.extend(load_err.into_env_errors());
+            Err(LoadError::new(env_errors))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn primary_name_wins_over_an_alias_even_when_both_are_set() {
+        env::set_var("ENV_MAP_TEST_RESOLVE_PRIMARY", "primary");
+        env::set_var("ENV_MAP_TEST_RESOLVE_ALIAS", "alias");
+
+        let result = resolve_var("ENV_MAP_TEST_RESOLVE_PRIMARY", &["ENV_MAP_TEST_RESOLVE_ALIAS"]);
+
+        env::remove_var("ENV_MAP_TEST_RESOLVE_PRIMARY");
+        env::remove_var("ENV_MAP_TEST_RESOLVE_ALIAS");
+
+        assert_eq!(result.unwrap(), "primary");
+    }
+
+    #[test]
+    fn not_present_only_when_the_primary_name_and_every_alias_are_absent() {
+        let aliases = [
+            "ENV_MAP_TEST_RESOLVE_MISSING_ALIAS_1",
+            "ENV_MAP_TEST_RESOLVE_MISSING_ALIAS_2",
+        ];
+
+        let result = resolve_var("ENV_MAP_TEST_RESOLVE_MISSING_PRIMARY", &aliases);
+        assert!(matches!(result, Err(VarError::NotPresent)));
+
+        env::set_var("ENV_MAP_TEST_RESOLVE_MISSING_ALIAS_2", "fallback");
+        let result = resolve_var("ENV_MAP_TEST_RESOLVE_MISSING_PRIMARY", &aliases);
+        env::remove_var("ENV_MAP_TEST_RESOLVE_MISSING_ALIAS_2");
+
+        assert_eq!(result.unwrap(), "fallback");
     }
 }
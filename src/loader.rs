@@ -0,0 +1,348 @@
+/*
+ * Copyright (c) 2019 Lucas Vasilakopoulos
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! A layered loader: register ordered [`Source`]s and merge them into a
+//! single [`EnvMap`], with each source overriding the ones merged before it.
+
+use crate::{dotenv, EnvError, EnvMap, EnvVars, LoadError};
+use std::collections::HashMap;
+use std::env;
+use std::env::VarError;
+use std::path::{Path, PathBuf};
+
+/// The `(key, value)` pairs a [`Source`] contributes, or the `(key, error)`
+/// pairs produced while trying to.
+pub type CollectResult = Result<Vec<(String, String)>, Vec<(String, EnvError)>>;
+
+/// A named layer of `(key, value)` pairs that can be merged into a
+/// [`Loader`].
+///
+/// Errors are keyed so they can be folded directly into a `LoadError`'s
+/// aggregated error map. That map holds one error per key: an error whose
+/// key is already present, from an earlier source or a missing required
+/// variable, replaces the earlier one.
+pub trait Source {
+    fn collect(&self) -> CollectResult;
+}
+
+/// A fixed set of `(key, value)` pairs, typically used as the lowest-
+/// precedence layer (e.g. inline defaults).
+pub struct Defaults<'a> {
+    entries: &'a [(&'a str, &'a str)],
+}
+
+impl<'a> Defaults<'a> {
+    pub fn new(entries: &'a [(&'a str, &'a str)]) -> Self {
+        Defaults { entries }
+    }
+}
+
+impl<'a> Source for Defaults<'a> {
+    fn collect(&self) -> CollectResult {
+        Ok(self
+            .entries
+            .iter()
+            .map(|&(key, value)| (key.to_string(), value.to_string()))
+            .collect())
+    }
+}
+
+/// A `.env` file, parsed with [`dotenv::parse_file`](crate::dotenv::parse_file).
+pub struct DotenvFile {
+    path: PathBuf,
+}
+
+impl DotenvFile {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        DotenvFile {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+}
+
+impl Source for DotenvFile {
+    fn collect(&self) -> CollectResult {
+        dotenv::parse_file(&self.path).map_err(|errors| {
+            errors
+                .into_iter()
+                .enumerate()
+                .map(|(index, error)| {
+                    (
+                        format!("{}:{}", self.path.display(), index),
+                        EnvError::Dotenv(error),
+                    )
+                })
+                .collect()
+        })
+    }
+}
+
+/// The ambient process environment, optionally restricted to variables
+/// carrying `prefix`, with `prefix` stripped from the resulting keys.
+#[derive(Default)]
+pub struct Environment {
+    prefix: Option<String>,
+}
+
+impl Environment {
+    /// Merges the *entire* process environment, unfiltered.
+    ///
+    /// Every variable visible to the process — including anything sensitive
+    /// sitting alongside configuration, such as credentials injected by a
+    /// deploy tool — ends up in the loaded map. Prefer [`Environment::with_prefix`]
+    /// unless a full dump is genuinely what's needed.
+    pub fn all() -> Self {
+        Environment { prefix: None }
+    }
+
+    /// Merges only variables whose name starts with `prefix`, stripping
+    /// `prefix` from the resulting keys. The scoped, recommended way to pull
+    /// from the process environment.
+    pub fn with_prefix(prefix: &str) -> Self {
+        Environment {
+            prefix: Some(prefix.to_string()),
+        }
+    }
+}
+
+impl Source for Environment {
+    fn collect(&self) -> CollectResult {
+        let mut entries = Vec::new();
+        let mut errors = Vec::new();
+
+        for (key, value) in env::vars_os() {
+            let key_display = key.to_string_lossy().into_owned();
+
+            if let Some(prefix) = &self.prefix {
+                if !key_display.starts_with(prefix.as_str()) {
+                    continue;
+                }
+            }
+
+            let key = match key.into_string() {
+                Ok(key) => key,
+                Err(raw) => {
+                    errors.push((key_display, EnvError::Var(VarError::NotUnicode(raw))));
+                    continue;
+                }
+            };
+
+            let value = match value.into_string() {
+                Ok(value) => value,
+                Err(raw) => {
+                    errors.push((key, EnvError::Var(VarError::NotUnicode(raw))));
+                    continue;
+                }
+            };
+
+            match &self.prefix {
+                Some(prefix) => {
+                    if let Some(stripped) = key.strip_prefix(prefix.as_str()) {
+                        entries.push((stripped.to_string(), value));
+                    }
+                }
+                None => entries.push((key, value)),
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(entries)
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+/// An owned copy of a [`VarSpec`] retained by [`Loader::require`], which
+/// outlives the borrowed `env_vars` map passed in by the caller.
+struct Requirement {
+    name: String,
+    aliases: Vec<String>,
+    default: Option<String>,
+}
+
+/// A builder that merges one or more [`Source`]s, in order, into a single
+/// [`EnvMap`]. Each merged source overrides keys set by sources merged
+/// before it, so the last source merged wins.
+///
+/// Examples:
+///
+/// ```no_run
+/// use env_map::{Defaults, Environment, Loader};
+///
+/// let env = Loader::new()
+///     .merge(Defaults::new(&[("PORT", "8080")]))
+///     .merge(Environment::with_prefix("APP_"))
+///     .load()
+///     .unwrap();
+/// ```
+#[derive(Default)]
+pub struct Loader {
+    sources: Vec<Box<dyn Source>>,
+    requirements: Vec<Requirement>,
+}
+
+impl Loader {
+    pub fn new() -> Self {
+        Loader {
+            sources: Vec::new(),
+            requirements: Vec::new(),
+        }
+    }
+
+    /// Registers `source` as the next, highest-precedence layer.
+    pub fn merge(mut self, source: impl Source + 'static) -> Self {
+        self.sources.push(Box::new(source));
+        self
+    }
+
+    /// Requires every variable named in `env_vars` to be present once all
+    /// sources are merged, the same [`VarSpec`](crate::VarSpec) a caller would pass to
+    /// [`load`](crate::load): a variable missing from the merged map falls
+    /// back to an alias already present in the map, then to `spec.default`,
+    /// then is reported as a missing-variable error alongside any source
+    /// errors.
+    ///
+    /// Examples:
+    ///
+    /// ```no_run
+    /// use env_map::{Environment, Loader, VarSpec};
+    /// use std::collections::HashMap;
+    ///
+    /// let required: HashMap<&str, VarSpec> = [("DATABASE_URL", VarSpec::new(None))]
+    ///     .iter()
+    ///     .cloned()
+    ///     .collect();
+    ///
+    /// let env = Loader::new()
+    ///     .merge(Environment::with_prefix("APP_"))
+    ///     .require(&required)
+    ///     .load()
+    ///     .unwrap();
+    /// ```
+    pub fn require<T>(mut self, env_vars: EnvVars<T>) -> Self {
+        for (&name, &spec) in env_vars {
+            self.requirements.push(Requirement {
+                name: name.to_string(),
+                aliases: spec.aliases.iter().map(|&alias| alias.to_string()).collect(),
+                default: spec.default.map(str::to_string),
+            });
+        }
+
+        self
+    }
+
+    /// Merges every registered source into a single [`EnvMap`], then checks
+    /// every variable registered with [`require`](Self::require), aggregating
+    /// errors from both steps into one [`LoadError`].
+    pub fn load(self) -> Result<EnvMap, LoadError> {
+        let mut env_map = EnvMap::new();
+        let mut env_errors = HashMap::new();
+
+        for source in self.sources {
+            match source.collect() {
+                Ok(entries) => {
+                    for (key, value) in entries {
+                        env_map.insert(key, value);
+                    }
+                }
+                Err(errors) => {
+                    for (key, error) in errors {
+                        env_errors.insert(key, error);
+                    }
+                }
+            }
+        }
+
+        for requirement in self.requirements {
+            if env_map.contains_key(&requirement.name) {
+                continue;
+            }
+
+            let fallback = requirement
+                .aliases
+                .iter()
+                .find_map(|alias| env_map.get(alias).cloned());
+
+            match fallback.or(requirement.default) {
+                Some(value) => {
+                    env_map.insert(requirement.name, value);
+                }
+                None => {
+                    env_errors.insert(requirement.name, EnvError::Var(VarError::NotPresent));
+                }
+            }
+        }
+
+        if env_errors.is_empty() {
+            Ok(env_map)
+        } else {
+            Err(LoadError::new(env_errors))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::VarSpec;
+
+    struct Failing;
+
+    impl Source for Failing {
+        fn collect(&self) -> CollectResult {
+            Err(vec![("BROKEN".to_string(), EnvError::Var(VarError::NotPresent))])
+        }
+    }
+
+    #[test]
+    fn later_sources_override_earlier_ones() {
+        let env = Loader::new()
+            .merge(Defaults::new(&[("PORT", "8080")]))
+            .merge(Defaults::new(&[("PORT", "9090")]))
+            .load()
+            .unwrap();
+
+        assert_eq!(env["PORT"], "9090");
+    }
+
+    #[test]
+    fn aggregates_source_errors_with_a_missing_required_key() {
+        let required: HashMap<&str, VarSpec> =
+            [("DATABASE_URL", VarSpec::new(None))].iter().cloned().collect();
+
+        let err = Loader::new()
+            .merge(Failing)
+            .require(&required)
+            .load()
+            .unwrap_err()
+            .into_env_errors();
+
+        assert!(err.contains_key("BROKEN"));
+        assert!(err.contains_key("DATABASE_URL"));
+    }
+
+    #[test]
+    fn required_alias_falls_back_to_a_previously_merged_key() {
+        let required: HashMap<&str, VarSpec> = [(
+            "DATABASE_URL",
+            VarSpec::with_aliases(&["LEGACY_DATABASE_URL"], None),
+        )]
+        .iter()
+        .cloned()
+        .collect();
+
+        let env = Loader::new()
+            .merge(Defaults::new(&[("LEGACY_DATABASE_URL", "postgres://legacy")]))
+            .require(&required)
+            .load()
+            .unwrap();
+
+        assert_eq!(env["DATABASE_URL"], "postgres://legacy");
+    }
+}
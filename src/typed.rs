@@ -0,0 +1,259 @@
+/*
+ * Copyright (c) 2019 Lucas Vasilakopoulos
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Typed access on top of the plain string `EnvMap` returned by [`load`](crate::load),
+//! for callers that want a `bool`, a number, or a delimited `Vec<T>` rather than
+//! a raw `String`.
+
+use crate::{load_internal, EnvError, EnvMap, EnvVars, LoadError};
+use std::collections::HashMap;
+use std::env::VarError;
+use std::str::FromStr;
+
+const DEFAULT_SEPARATOR: &str = ",";
+
+/// A check that a loaded value can be coerced to the type a caller expects.
+///
+/// Built with [`bool_validator`], [`number_validator`], or [`vec_validator`].
+pub type Validator = Box<dyn Fn(&str) -> Result<(), String>>;
+
+/// A validator that accepts the same case-insensitive boolean spellings as
+/// [`TypedEnv::get_bool`].
+pub fn bool_validator() -> Validator {
+    Box::new(|value| match value.to_lowercase().as_str() {
+        "true" | "yes" | "on" | "1" | "false" | "no" | "off" | "0" => Ok(()),
+        _ => Err(format!("'{}' is not a valid boolean", value)),
+    })
+}
+
+/// A validator that checks a value parses as `T`, e.g. `number_validator::<i64>()`.
+pub fn number_validator<T: FromStr>() -> Validator {
+    Box::new(|value| {
+        value
+            .parse::<T>()
+            .map(|_| ())
+            .map_err(|_| format!("'{}' is not a valid number", value))
+    })
+}
+
+/// A validator that checks a value splits on `separator` into elements that
+/// each parse as `T`, e.g. `vec_validator::<i64>(",")`.
+pub fn vec_validator<T: FromStr>(separator: &str) -> Validator {
+    let separator = separator.to_string();
+
+    Box::new(move |value| {
+        for element in value.split(separator.as_str()) {
+            element
+                .parse::<T>()
+                .map_err(|_| format!("unable to parse element '{}'", element))?;
+        }
+
+        Ok(())
+    })
+}
+
+/// Loads variables the same way [`load`](crate::load) does, but eagerly
+/// validates each named variable in `kinds` against its validator and
+/// returns a [`TypedEnv`] that can coerce values into `bool`, numeric, or
+/// `Vec<T>` types on demand.
+///
+/// A value that fails its validator is folded into the same `LoadError`
+/// alongside any missing-required-variable errors, so callers get one
+/// aggregated error map covering both problems.
+///
+/// Examples:
+///
+/// ```
+/// use env_map::{load_typed, number_validator, VarSpec};
+/// use std::collections::HashMap;
+///
+/// let env_vars: HashMap<&str, VarSpec> = [("PORT", VarSpec::new(Some("8080")))]
+///     .iter()
+///     .cloned()
+///     .collect();
+///
+/// let mut kinds: HashMap<&str, _> = HashMap::new();
+/// kinds.insert("PORT", number_validator::<u16>());
+///
+/// let env = load_typed(&env_vars, &kinds).unwrap();
+/// let port: u16 = env.get_number("PORT").unwrap();
+/// ```
+pub fn load_typed<T>(
+    env_vars: EnvVars<T>,
+    kinds: &HashMap<&str, Validator>,
+) -> Result<TypedEnv, LoadError> {
+    let env_map = load_internal(
+        env_vars,
+        true,
+        |_, _| None,
+        |name, value| match kinds.get(name) {
+            Some(validator) => validator(value).map_err(EnvError::Parse),
+            None => Ok(()),
+        },
+    )?;
+
+    Ok(TypedEnv(env_map))
+}
+
+/// A wrapper around an [`EnvMap`](crate) that coerces string values into other
+/// types, returning an [`EnvError::Parse`] when a value cannot be interpreted
+/// as the requested type.
+#[derive(Debug)]
+pub struct TypedEnv(EnvMap);
+
+impl TypedEnv {
+    /// Parses the value for `key` as a `bool`.
+    ///
+    /// Recognizes `true`/`false`, `yes`/`no`, `on`/`off` and `1`/`0`, matched
+    /// case-insensitively.
+    pub fn get_bool(&self, key: &str) -> Result<bool, EnvError> {
+        let value = self.value(key)?;
+
+        match value.to_lowercase().as_str() {
+            "true" | "yes" | "on" | "1" => Ok(true),
+            "false" | "no" | "off" | "0" => Ok(false),
+            _ => Err(EnvError::Parse(format!(
+                "'{}' is not a valid boolean",
+                value
+            ))),
+        }
+    }
+
+    /// Parses the value for `key` into any `T: FromStr`, such as an integer or
+    /// floating-point type.
+    pub fn get_number<T: FromStr>(&self, key: &str) -> Result<T, EnvError> {
+        let value = self.value(key)?;
+
+        value
+            .parse()
+            .map_err(|_| EnvError::Parse(format!("'{}' is not a valid number", value)))
+    }
+
+    /// Splits the value for `key` on the default separator (`,`) and parses
+    /// each element into `T: FromStr`.
+    ///
+    /// Every element that fails to parse is collected, and all such failures
+    /// are listed together in a single [`EnvError::Parse`].
+    pub fn get_vec<T: FromStr>(&self, key: &str) -> Result<Vec<T>, EnvError> {
+        self.get_vec_with_separator(key, DEFAULT_SEPARATOR)
+    }
+
+    /// Like [`get_vec`](Self::get_vec), but splits on `separator` instead of
+    /// the default `,`.
+    pub fn get_vec_with_separator<T: FromStr>(
+        &self,
+        key: &str,
+        separator: &str,
+    ) -> Result<Vec<T>, EnvError> {
+        let value = self.value(key)?;
+
+        let mut elements = Vec::new();
+        let mut failures = Vec::new();
+
+        for element in value.split(separator) {
+            match element.parse() {
+                Ok(parsed) => elements.push(parsed),
+                Err(_) => failures.push(element.to_string()),
+            }
+        }
+
+        if failures.is_empty() {
+            Ok(elements)
+        } else {
+            Err(EnvError::Parse(format!(
+                "unable to parse element(s) of '{}': {}",
+                key,
+                failures.join(", ")
+            )))
+        }
+    }
+
+    /// Returns the inner map of raw string values.
+    pub fn into_inner(self) -> EnvMap {
+        self.0
+    }
+
+    fn value(&self, key: &str) -> Result<&str, EnvError> {
+        self.0
+            .get(key)
+            .map(String::as_str)
+            .ok_or(EnvError::Var(VarError::NotPresent))
+    }
+}
+
+impl From<HashMap<String, String>> for TypedEnv {
+    fn from(env_map: EnvMap) -> Self {
+        TypedEnv(env_map)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::VarSpec;
+
+    #[test]
+    fn bool_validator_rejects_an_invalid_spelling() {
+        let validate = bool_validator();
+
+        assert!(validate("yes").is_ok());
+        assert!(validate("nope").is_err());
+    }
+
+    #[test]
+    fn get_bool_rejects_an_invalid_value() {
+        let mut map = HashMap::new();
+        map.insert("FLAG".to_string(), "nope".to_string());
+        let env = TypedEnv::from(map);
+
+        assert!(matches!(env.get_bool("FLAG"), Err(EnvError::Parse(_))));
+    }
+
+    #[test]
+    fn get_vec_collects_every_bad_element_instead_of_short_circuiting() {
+        let mut map = HashMap::new();
+        map.insert("NUMS".to_string(), "1,x,3,y".to_string());
+        let env = TypedEnv::from(map);
+
+        match env.get_vec::<i64>("NUMS") {
+            Err(EnvError::Parse(message)) => {
+                assert!(message.contains('x'));
+                assert!(message.contains('y'));
+            }
+            other => panic!("expected a Parse error listing every bad element, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn load_typed_aggregates_a_bad_type_alongside_a_missing_required_variable() {
+        let env_vars: HashMap<&str, VarSpec> = [
+            ("ENV_MAP_TEST_TYPED_MISSING", VarSpec::new(None)),
+            (
+                "ENV_MAP_TEST_TYPED_BAD_TYPE",
+                VarSpec::new(Some("not-a-number")),
+            ),
+        ]
+        .iter()
+        .cloned()
+        .collect();
+
+        let mut kinds: HashMap<&str, Validator> = HashMap::new();
+        kinds.insert("ENV_MAP_TEST_TYPED_BAD_TYPE", number_validator::<u16>());
+
+        let env_errors = load_typed(&env_vars, &kinds).unwrap_err().into_env_errors();
+
+        assert!(matches!(
+            env_errors.get("ENV_MAP_TEST_TYPED_MISSING"),
+            Some(EnvError::Var(VarError::NotPresent))
+        ));
+        assert!(matches!(
+            env_errors.get("ENV_MAP_TEST_TYPED_BAD_TYPE"),
+            Some(EnvError::Parse(_))
+        ));
+    }
+}